@@ -0,0 +1,140 @@
+//! Content-sniffing for video containers, independent of file extension.
+
+/// Number of leading bytes needed to recognize any container handled here.
+pub const SNIFF_LEN: usize = 16;
+
+/// A video container recognized from its leading bytes ("magic bytes"),
+/// rather than trusted from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// ISO base media file format: MP4, MOV, M4V all share this signature.
+    IsoBmff,
+    /// EBML-based container: MKV or WebM. The signature alone can't tell them apart.
+    Ebml,
+    Avi,
+    Flv,
+    /// MPEG program stream or elementary stream.
+    Mpeg,
+}
+
+impl VideoFormat {
+    /// A best-guess `Content-Type` for this format, when unambiguous.
+    ///
+    /// Returns `None` for containers (like [`VideoFormat::Ebml`]) whose exact
+    /// flavor can't be told apart from the signature alone; callers should
+    /// fall back to extension-based detection in that case.
+    pub fn content_type(&self) -> Option<&'static str> {
+        match self {
+            VideoFormat::IsoBmff => Some("video/mp4"),
+            VideoFormat::Avi => Some("video/x-msvideo"),
+            VideoFormat::Flv => Some("video/x-flv"),
+            VideoFormat::Mpeg => Some("video/mpeg"),
+            VideoFormat::Ebml => None,
+        }
+    }
+
+    /// A reasonable file extension for this format, used to name extensionless
+    /// files recognized only by their magic bytes.
+    pub fn default_extension(&self) -> &'static str {
+        match self {
+            VideoFormat::IsoBmff => "mp4",
+            VideoFormat::Ebml => "mkv",
+            VideoFormat::Avi => "avi",
+            VideoFormat::Flv => "flv",
+            VideoFormat::Mpeg => "mpg",
+        }
+    }
+}
+
+/// Inspect the leading bytes of a file and recognize its container format, if any.
+///
+/// `header` should hold up to [`SNIFF_LEN`] bytes read from the start of the file;
+/// shorter slices are handled gracefully: a signature longer than the slice
+/// simply doesn't match, rather than panicking.
+pub fn determine_format(header: &[u8]) -> Option<VideoFormat> {
+    if header.len() >= 8 && &header[4..8] == b"ftyp" {
+        return Some(VideoFormat::IsoBmff);
+    }
+    if header.len() >= 4 && header[0..4] == [0x1A, 0x45, 0xDF, 0xA3] {
+        return Some(VideoFormat::Ebml);
+    }
+    if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"AVI " {
+        return Some(VideoFormat::Avi);
+    }
+    if header.len() >= 3 && &header[0..3] == b"FLV" {
+        return Some(VideoFormat::Flv);
+    }
+    if header.len() >= 4
+        && header[0] == 0x00
+        && header[1] == 0x00
+        && header[2] == 0x01
+        && (header[3] == 0xBA || header[3] == 0xB3)
+    {
+        return Some(VideoFormat::Mpeg);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_iso_bmff() {
+        let mut header = [0u8; SNIFF_LEN];
+        header[4..8].copy_from_slice(b"ftyp");
+        assert_eq!(determine_format(&header), Some(VideoFormat::IsoBmff));
+    }
+
+    #[test]
+    fn recognizes_ebml() {
+        let header = [0x1A, 0x45, 0xDF, 0xA3];
+        assert_eq!(determine_format(&header), Some(VideoFormat::Ebml));
+    }
+
+    #[test]
+    fn recognizes_avi() {
+        let mut header = [0u8; 12];
+        header[0..4].copy_from_slice(b"RIFF");
+        header[8..12].copy_from_slice(b"AVI ");
+        assert_eq!(determine_format(&header), Some(VideoFormat::Avi));
+    }
+
+    #[test]
+    fn recognizes_flv() {
+        assert_eq!(determine_format(b"FLV\x01"), Some(VideoFormat::Flv));
+    }
+
+    #[test]
+    fn recognizes_mpeg_program_stream() {
+        assert_eq!(
+            determine_format(&[0x00, 0x00, 0x01, 0xBA]),
+            Some(VideoFormat::Mpeg)
+        );
+    }
+
+    #[test]
+    fn recognizes_mpeg_elementary_stream() {
+        assert_eq!(
+            determine_format(&[0x00, 0x00, 0x01, 0xB3]),
+            Some(VideoFormat::Mpeg)
+        );
+    }
+
+    #[test]
+    fn unrecognized_header_is_none() {
+        assert_eq!(determine_format(b"not a video header"), None);
+    }
+
+    #[test]
+    fn header_shorter_than_any_signature_is_none_not_a_panic() {
+        assert_eq!(determine_format(&[]), None);
+        assert_eq!(determine_format(&[0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn ebml_content_type_is_ambiguous() {
+        assert_eq!(VideoFormat::Ebml.content_type(), None);
+        assert_eq!(VideoFormat::Ebml.default_extension(), "mkv");
+    }
+}
@@ -1,5 +1,6 @@
 use clap::Parser;
 use lazy_static::lazy_static;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
@@ -9,6 +10,12 @@ use std::{
     },
 };
 
+pub mod format;
+pub mod range;
+pub mod thumbnail;
+pub mod transcode;
+pub mod tree;
+
 lazy_static! {
     pub static ref VIDEO_EXTENSIONS: Vec<String> = vec![
         "mp4".into(),
@@ -24,6 +31,12 @@ lazy_static! {
         "wmv".into(),
         "3gp".into()
     ];
+
+    /// Extensions that `<video>` can play directly in every major browser.
+    /// Anything else needs transcoding when `VideoPlayerConfig::enable_transcoding`
+    /// is turned on.
+    pub static ref WEB_PLAYABLE_EXTENSIONS: Vec<String> =
+        vec!["mp4".into(), "webm".into(), "m4v".into()];
 }
 
 /// Configuration for the video server.
@@ -37,6 +50,34 @@ pub struct VideoPlayerConfig {
 
     #[clap(short, long, default_value = "0.0.0.0")]
     pub host: String,
+
+    /// Maximum accepted body size for `POST /upload`, in bytes.
+    #[clap(long, default_value = "1073741824")]
+    pub max_upload_bytes: usize,
+
+    /// Directory generated poster thumbnails are cached under.
+    #[clap(long, default_value = ".thumbnails")]
+    pub thumbnail_cache_dir: String,
+
+    /// Offset in seconds into each video to extract its poster thumbnail from.
+    #[clap(long, default_value = "5")]
+    pub thumbnail_offset_secs: u32,
+
+    /// Transcode containers the browser can't play natively to fragmented MP4
+    /// on the fly, instead of serving (and failing to play) the original file.
+    #[clap(long)]
+    pub enable_transcoding: bool,
+
+    /// Watch the asset root for filesystem changes and incrementally index new
+    /// or removed files, instead of requiring a manual `POST /reload`.
+    #[clap(long)]
+    pub enable_watch: bool,
+
+    /// How public URLs for videos are derived: `flat` opaque `{index}.{ext}`
+    /// ids, or `hierarchical` paths that mirror the on-disk folder structure
+    /// under `assets_root` and render the index as a navigable tree.
+    #[clap(long, value_enum, default_value_t = PathMode::Flat)]
+    pub path_mode: PathMode,
 }
 
 impl Default for VideoPlayerConfig {
@@ -45,17 +86,102 @@ impl Default for VideoPlayerConfig {
             assets_root: "assets".to_string(),
             port: 9092,
             host: "0.0.0.0".to_string(),
+            max_upload_bytes: 1024 * 1024 * 1024,
+            thumbnail_cache_dir: ".thumbnails".to_string(),
+            thumbnail_offset_secs: 5,
+            enable_transcoding: false,
+            enable_watch: false,
+            path_mode: PathMode::Flat,
         }
     }
 }
 
+/// How a video's public server path is derived from its location on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PathMode {
+    /// Flatten everything into opaque `{index}.{ext}` server paths.
+    Flat,
+    /// Keep the relative path from `assets_root` (percent-encoded into a
+    /// single URL segment) as the server path, and group the index page into
+    /// a folder tree instead of a flat grid.
+    Hierarchical,
+}
+
+impl Default for PathMode {
+    fn default() -> Self {
+        PathMode::Flat
+    }
+}
+
+/// Display name and path relative to the asset root (folders and file name)
+/// for a video, populated only when
+/// [`PathMode::Hierarchical`] is in effect, so the index template can group
+/// videos into a navigable folder tree.
+#[derive(Debug, Clone)]
+pub struct VideoMetadata {
+    pub display_name: String,
+    pub relative_path: String,
+}
+
 /// Shared state for the video server, including video indexing.
 #[derive(Default)]
 pub struct VideoPlayerState {
     pub videos: HashMap<String, String>,
+    /// Container format detected by content-sniffing, keyed by server path.
+    /// Absent when sniffing didn't recognize the file (extension-only match).
+    pub formats: HashMap<String, format::VideoFormat>,
+    /// Poster thumbnails generated off the request path, keyed by server path.
+    ///
+    /// Kept behind its own lock (rather than just a plain field) so the background
+    /// task `load_video` spawns to generate a thumbnail can populate this without
+    /// needing the whole [`SharedState`] lock.
+    pub thumbnails: Arc<Mutex<HashMap<String, PathBuf>>>,
+    /// Display name and path relative to the asset root for each video, keyed
+    /// by server path. Only populated in [`PathMode::Hierarchical`].
+    pub metadata: HashMap<String, VideoMetadata>,
     video_extensions: HashSet<String>,
     next_index: AtomicUsize,
     root: Option<String>,
+    thumbnail_cache_dir: Option<PathBuf>,
+    thumbnail_offset_secs: u32,
+    transcode_enabled: bool,
+    watch_enabled: bool,
+    path_mode: PathMode,
+}
+
+/// Lock the shared thumbnail cache, logging (rather than panicking) if it was
+/// poisoned by a panic in another thread holding it.
+fn lock_thumbnails(
+    thumbnails: &Mutex<HashMap<String, PathBuf>>,
+) -> Option<std::sync::MutexGuard<'_, HashMap<String, PathBuf>>> {
+    match thumbnails.lock() {
+        Ok(guard) => Some(guard),
+        Err(_) => {
+            eprintln!("Warning: thumbnails lock was poisoned, skipping thumbnail cache update");
+            None
+        }
+    }
+}
+
+/// Whether `path`'s file name starts with `.`, following the usual Unix
+/// convention for hidden files and directories (`.git`, `.DS_Store`, the
+/// `.transcoded` cache folder this crate writes under the asset root, ...).
+fn is_hidden<P: AsRef<std::path::Path>>(path: P) -> bool {
+    path.as_ref()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// Read up to [`format::SNIFF_LEN`] bytes from the start of a file and try to
+/// recognize its container format.
+fn sniff_format<P: AsRef<std::path::Path>>(path: P) -> Option<format::VideoFormat> {
+    use std::io::Read;
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut header = [0u8; format::SNIFF_LEN];
+    let read = file.read(&mut header).ok()?;
+    format::determine_format(&header[..read])
 }
 
 pub type SharedState = Arc<Mutex<VideoPlayerState>>;
@@ -76,14 +202,119 @@ impl VideoPlayerState {
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     }
 
-    /// Check if a file path is a supported video file.
-    pub fn is_video_file<P: AsRef<std::path::Path>>(&self, path: P) -> bool {
-        if let Some(extension) = path.as_ref().extension() {
-            if self.video_extensions.contains(extension.to_str().unwrap()) {
-                return true;
+    /// Root directory this state indexes videos from, if configured.
+    pub fn root(&self) -> Option<&str> {
+        self.root.as_deref()
+    }
+
+    /// Directory thumbnails are cached under, if configured.
+    pub fn thumbnail_cache_dir(&self) -> Option<&std::path::Path> {
+        self.thumbnail_cache_dir.as_deref()
+    }
+
+    /// Whether on-the-fly transcoding of non-web-playable containers is enabled.
+    pub fn transcode_enabled(&self) -> bool {
+        self.transcode_enabled
+    }
+
+    /// Whether the asset root should be watched for incremental re-indexing.
+    pub fn watch_enabled(&self) -> bool {
+        self.watch_enabled
+    }
+
+    /// How server paths are derived: flat opaque ids, or hierarchical paths
+    /// mirroring the on-disk folder structure.
+    pub fn path_mode(&self) -> PathMode {
+        self.path_mode
+    }
+
+    /// Re-derive the map key a `:video_id` route param was stored under.
+    ///
+    /// axum's `Path<String>` extractor percent-decodes the captured segment
+    /// before handlers see it, but in [`PathMode::Hierarchical`] the
+    /// `videos`/`formats`/`metadata`/`thumbnails` maps are keyed by the
+    /// still-*encoded* server path (so it fits axum's single-segment route —
+    /// see [`VideoPlayerState::load_video`]). Re-applying the same encoding
+    /// here undoes axum's decode so lookups hit. A no-op in
+    /// [`PathMode::Flat`], where ids are never encoded in the first place.
+    pub fn canonical_video_id(&self, video_id: &str) -> String {
+        match self.path_mode {
+            PathMode::Flat => video_id.to_string(),
+            PathMode::Hierarchical => utf8_percent_encode(video_id, NON_ALPHANUMERIC).to_string(),
+        }
+    }
+
+    /// `path`'s components relative to the configured root, joined with `/`.
+    ///
+    /// Returns `None` if no root is configured, `path` isn't actually under
+    /// it, or any component isn't a plain directory/file name (e.g. a `..`
+    /// introduced by a symlink escaping the root) — guarding against the
+    /// resulting server path ever climbing back out of the asset root.
+    fn relative_path_string<P: AsRef<std::path::Path>>(&self, path: P) -> Option<String> {
+        let root = self.root.as_deref()?;
+        let relative = path.as_ref().strip_prefix(root).ok()?;
+        let mut parts = Vec::new();
+        for component in relative.components() {
+            match component {
+                std::path::Component::Normal(part) => parts.push(part.to_str()?.to_string()),
+                _ => return None,
             }
         }
-        false
+        (!parts.is_empty()).then(|| parts.join("/"))
+    }
+
+    /// Check whether a file can be played directly by `<video>` in a browser,
+    /// based on its extension.
+    pub fn is_web_playable<P: AsRef<std::path::Path>>(&self, path: P) -> bool {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| WEB_PLAYABLE_EXTENSIONS.iter().any(|known| known == ext))
+            .unwrap_or(false)
+    }
+
+    /// Check if a file path has a supported video extension.
+    ///
+    /// This is purely extension-based; use [`VideoPlayerState::is_video_file_sniffed`]
+    /// to also recognize known containers by their magic bytes. A non-UTF-8
+    /// extension is treated as "not a video file" rather than panicking.
+    pub fn is_video_file<P: AsRef<std::path::Path>>(&self, path: P) -> bool {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| self.video_extensions.contains(ext))
+            .unwrap_or(false)
+    }
+
+    /// Check if a file is a supported video, sniffing its magic bytes first and
+    /// falling back to the extension when the header isn't recognized.
+    pub fn is_video_file_sniffed<P: AsRef<std::path::Path>>(&self, path: P) -> bool {
+        sniff_format(path.as_ref()).is_some() || self.is_video_file(path)
+    }
+
+    /// Whether `path`'s file name starts with `.`, the same hidden-entry
+    /// convention [`VideoPlayerState::visit_dirs`] applies — so callers
+    /// reacting to individual filesystem events (e.g. the `--enable-watch`
+    /// watcher) can skip them too, instead of only a full directory walk.
+    pub fn is_hidden_path<P: AsRef<std::path::Path>>(&self, path: P) -> bool {
+        is_hidden(path)
+    }
+
+    /// Guess the HTTP `Content-Type` to serve a video under, based on its extension.
+    pub fn content_type_for_extension(extension: &str) -> &'static str {
+        match extension.to_ascii_lowercase().as_str() {
+            "mp4" | "m4v" => "video/mp4",
+            "webm" => "video/webm",
+            "mkv" => "video/x-matroska",
+            "mov" => "video/quicktime",
+            "avi" => "video/x-msvideo",
+            "flv" => "video/x-flv",
+            "wmv" => "video/x-ms-wmv",
+            "mpg" | "mpeg" => "video/mpeg",
+            "3gp" => "video/3gpp",
+            "heic" => "image/heic",
+            _ => "application/octet-stream",
+        }
     }
 
     /// Load videos from a specified directory path.
@@ -91,28 +322,129 @@ impl VideoPlayerState {
         self.visit_dirs(root)
     }
 
-    /// Load a video from a file path.
-    pub fn load_video(&mut self, path: PathBuf) {
-        let stored_file_name = path.to_str().unwrap().to_string();
-        let extension = path.extension().unwrap();
-        // make server path {id}.{ext}
-        // if the first loaded video is an mp4 file,
-        // the server path would be "0.mp4"
-        // if the next is mov,
-        // the server path would be "1.mov"
-        let server_path = format!(
-            "{}.{}",
-            self.next_index.load(Ordering::SeqCst),
-            extension.to_str().unwrap()
-        );
+    /// Load a video from a file path, returning the server path it was registered
+    /// under: `{id}.{ext}`, or in [`PathMode::Hierarchical`] the percent-encoded
+    /// path relative to the root.
+    ///
+    /// Returns `None` instead of panicking (logging a warning) when the path isn't
+    /// valid UTF-8, or when it has no extension and content-sniffing couldn't
+    /// guess one either.
+    pub fn load_video(&mut self, path: PathBuf) -> Option<String> {
+        let Some(stored_file_name) = path.to_str().map(|s| s.to_string()) else {
+            eprintln!(
+                "Skipping video with non-UTF-8 path: {}",
+                path.to_string_lossy()
+            );
+            return None;
+        };
+
+        let format = sniff_format(&path);
+        let extension = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(extension) => extension.to_string(),
+            None => match format.map(|format| format.default_extension()) {
+                Some(extension) => extension.to_string(),
+                None => {
+                    eprintln!(
+                        "Skipping video with no extension and unrecognized container: {}",
+                        stored_file_name
+                    );
+                    return None;
+                }
+            },
+        };
+
+        let relative_path = (self.path_mode == PathMode::Hierarchical)
+            .then(|| self.relative_path_string(&path))
+            .flatten();
+
+        // In hierarchical mode the server path is the relative path from root,
+        // percent-encoded into a single opaque segment so it still fits axum's
+        // single-segment `:video_id` route. Otherwise (or if there's no root
+        // configured, or `path` escapes it) fall back to the flat {id}.{ext}
+        // scheme: if the first loaded video is an mp4 file, the server path
+        // would be "0.mp4"; if the next is mov, "1.mov".
+        let server_path = relative_path
+            .as_deref()
+            .map(|relative| utf8_percent_encode(relative, NON_ALPHANUMERIC).to_string())
+            .unwrap_or_else(|| format!("{}.{}", self.next_index.load(Ordering::SeqCst), extension));
+
         println!("Loading video: {} as {}", stored_file_name, server_path);
         // increase index for next video
         self.advance_index();
+        if let Some(format) = format {
+            self.formats.insert(server_path.clone(), format);
+        }
+        if let Some(relative_path) = relative_path {
+            let display_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| server_path.clone());
+            self.metadata.insert(
+                server_path.clone(),
+                VideoMetadata {
+                    display_name,
+                    relative_path,
+                },
+            );
+        }
         // mapping used by axum router
-        self.videos.insert(server_path, stored_file_name);
+        self.videos.insert(server_path.clone(), stored_file_name);
+        self.spawn_thumbnail_generation(path, server_path.clone());
+        Some(server_path)
+    }
+
+    /// Remove the video stored at `path`, if any, returning the server path it
+    /// was registered under. Used to incrementally react to a delete event from
+    /// the filesystem watcher, without a full [`VideoPlayerState::reload`].
+    pub fn remove_video_by_path<P: AsRef<std::path::Path>>(&mut self, path: P) -> Option<String> {
+        let path = path.as_ref().to_str()?;
+        let server_path = self
+            .videos
+            .iter()
+            .find(|(_, stored_file_name)| stored_file_name.as_str() == path)
+            .map(|(server_path, _)| server_path.clone())?;
+        self.videos.remove(&server_path);
+        self.formats.remove(&server_path);
+        self.metadata.remove(&server_path);
+        if let Some(mut thumbnails) = lock_thumbnails(&self.thumbnails) {
+            thumbnails.remove(&server_path);
+        }
+        Some(server_path)
+    }
+
+    /// Kick off background thumbnail extraction for a freshly loaded video, if a
+    /// cache directory is configured. Best-effort: nothing is ever propagated,
+    /// since the placeholder image covers for a missing thumbnail. I/O errors
+    /// are logged; a missing `ffmpeg` binary or an unsuccessful `ffmpeg` run
+    /// just leaves the video without a thumbnail, without logging.
+    fn spawn_thumbnail_generation(&self, source: PathBuf, server_path: String) {
+        let Some(cache_dir) = self.thumbnail_cache_dir.clone() else {
+            return;
+        };
+        let thumbnails = Arc::clone(&self.thumbnails);
+        let offset_secs = self.thumbnail_offset_secs;
+        tokio::spawn(async move {
+            match thumbnail::generate_thumbnail(&source, &cache_dir, &server_path, offset_secs)
+                .await
+            {
+                Ok(Some(thumbnail_path)) => {
+                    if let Some(mut thumbnails) = lock_thumbnails(&thumbnails) {
+                        thumbnails.insert(server_path, thumbnail_path);
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    eprintln!("Failed to generate thumbnail for {}: {}", server_path, err)
+                }
+            }
+        });
     }
 
     /// Recursively visit all directories and load videos from them.
+    ///
+    /// Hidden entries (dotfiles, and directories like the `.transcoded` cache
+    /// folder that lives under the asset root) are skipped, the same
+    /// convention as a typical directory listing.
     pub fn visit_dirs<P: AsRef<std::path::Path>>(&mut self, root: P) -> std::io::Result<()> {
         if root.as_ref().is_dir() {
             // if given path is valid directory
@@ -120,13 +452,16 @@ impl VideoPlayerState {
                 for entry in dir {
                     let entry = entry?;
                     let path = entry.path();
+                    if is_hidden(&path) {
+                        continue;
+                    }
                     // if entry within directory is another directory
                     if path.is_dir() {
                         // call self recursively
                         self.visit_dirs(path)?;
                     }
-                    // otherwise, if is video file
-                    else if self.is_video_file(path.as_path()) {
+                    // otherwise, if is video file (by magic bytes, falling back to extension)
+                    else if self.is_video_file_sniffed(path.as_path()) {
                         // load video
                         self.load_video(path);
                     }
@@ -141,14 +476,32 @@ impl VideoPlayerState {
     pub fn build(config: &VideoPlayerConfig) -> Self {
         let mut state = Self::new();
         state.root = Some(config.assets_root.clone());
+        state.thumbnail_cache_dir = Some(PathBuf::from(&config.thumbnail_cache_dir));
+        state.thumbnail_offset_secs = config.thumbnail_offset_secs;
+        state.transcode_enabled = config.enable_transcoding;
+        state.watch_enabled = config.enable_watch;
+        state.path_mode = config.path_mode;
         state.load_videos(state.root.clone().unwrap()).unwrap();
         state
     }
 
     /// Reload the video index state, resetting the index and clearing the video list.
-    pub fn reload(&mut self) {
+    ///
+    /// Returns the underlying I/O error (rather than panicking) if the root
+    /// directory can't be read, so callers (e.g. `POST /reload`) can turn it
+    /// into a `500` instead of taking the whole worker down.
+    pub fn reload(&mut self) -> std::io::Result<()> {
         self.next_index = AtomicUsize::new(0);
         self.videos.clear();
-        self.load_videos(self.root.clone().unwrap()).unwrap();
+        self.formats.clear();
+        self.metadata.clear();
+        if let Some(mut thumbnails) = lock_thumbnails(&self.thumbnails) {
+            thumbnails.clear();
+        }
+        let root = self.root.clone().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::Other, "no root directory configured")
+        })?;
+        std::fs::read_dir(&root)?;
+        self.load_videos(root)
     }
 }
@@ -1,22 +1,93 @@
 use askama::Template;
+use async_stream::try_stream;
 use axum::{
-    body::{boxed, Body, BoxBody},
-    extract::{Path, State},
-    http::{Request, Response, StatusCode},
+    body::{boxed, Body, BoxBody, StreamBody},
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::{header, HeaderMap, Request, Response, StatusCode},
     response::{Html, IntoResponse, Redirect},
     routing::{get, get_service, post},
-    Router,
+    Json, Router,
 };
+use clap::Parser;
+use futures_util::TryStreamExt;
+use serde::Serialize;
 use std::{
     collections::HashMap,
     net::SocketAddr,
-    path::PathBuf,
+    path::{Path as StdPath, PathBuf},
     sync::{Arc, Mutex},
 };
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::{ReaderStream, StreamReader};
 use tower::ServiceExt;
 use tower_http::{services::ServeDir, trace::TraceLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use video_server::*;
+use video_server::{
+    range::{self, RangeParseResult},
+    transcode, tree, *,
+};
+
+/// Errors surfaced to clients as proper HTTP responses, instead of panicking
+/// (and taking the whole worker down) on bad input or a poisoned lock.
+#[derive(Debug)]
+pub enum AppError {
+    /// No video is registered under the requested id.
+    VideoNotFound(String),
+    /// The client's request was malformed in a way only it can fix.
+    BadRequest(String),
+    /// The uploaded or requested file's type isn't supported.
+    UnsupportedMediaType(String),
+    /// An I/O failure or other internal error; the cause is logged server-side
+    /// but not leaked to the client.
+    Internal(String),
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::VideoNotFound(id) => write!(f, "no video with id: {}", id),
+            AppError::BadRequest(msg) => write!(f, "bad request: {}", msg),
+            AppError::UnsupportedMediaType(msg) => write!(f, "unsupported media type: {}", msg),
+            AppError::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::Internal(err.to_string())
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> axum::response::Response {
+        if let AppError::Internal(ref cause) = self {
+            eprintln!("Internal error: {}", cause);
+        }
+        let (status, body) = match self {
+            AppError::VideoNotFound(id) => {
+                (StatusCode::NOT_FOUND, format!("No video with id: {}", id))
+            }
+            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg),
+            AppError::Internal(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string(),
+            ),
+        };
+        (status, body).into_response()
+    }
+}
+
+/// Lock a shared mutex, turning lock poisoning into a `500` instead of
+/// propagating the panic that poisoned it in the first place.
+fn lock_mutex<T>(mutex: &Mutex<T>) -> Result<std::sync::MutexGuard<'_, T>, AppError> {
+    mutex
+        .lock()
+        .map_err(|_| AppError::Internal("a shared lock was poisoned".to_string()))
+}
 
 struct HtmlTemplate<T>(T);
 
@@ -24,6 +95,10 @@ struct HtmlTemplate<T>(T);
 #[template(path = "index.html")]
 pub struct IndexTemplate {
     pub videos: HashMap<String, PathBuf>,
+    /// Folder tree to render instead of `videos`, in [`PathMode::Hierarchical`],
+    /// flattened into pre-order rows since askama can't render a
+    /// [`tree::DirectoryNode`] with a recursive macro call.
+    pub tree_rows: Option<Vec<tree::TreeRow>>,
 }
 
 impl<T> IntoResponse for HtmlTemplate<T>
@@ -42,18 +117,35 @@ where
     }
 }
 
-pub async fn index(State(state): State<SharedState>) -> impl IntoResponse {
-    let template = IndexTemplate {
-        videos: state
-            .lock()
-            .unwrap()
-            .videos
-            .clone()
-            .into_iter()
-            .map(|(k, v)| (k, PathBuf::from(v)))
-            .collect(),
+pub async fn index(State(state): State<SharedState>) -> Result<impl IntoResponse, AppError> {
+    let state = lock_mutex(&state)?;
+    let template = match state.path_mode() {
+        PathMode::Flat => IndexTemplate {
+            videos: state
+                .videos
+                .clone()
+                .into_iter()
+                .map(|(k, v)| (k, PathBuf::from(v)))
+                .collect(),
+            tree_rows: None,
+        },
+        PathMode::Hierarchical => {
+            let tree = tree::build_tree(state.metadata.iter().map(|(server_path, meta)| {
+                (
+                    meta.relative_path.as_str(),
+                    tree::VideoEntry {
+                        server_path: server_path.clone(),
+                        display_name: meta.display_name.clone(),
+                    },
+                )
+            }));
+            IndexTemplate {
+                videos: HashMap::new(),
+                tree_rows: Some(tree::flatten_tree(&tree)),
+            }
+        }
     };
-    HtmlTemplate(template)
+    Ok(HtmlTemplate(template))
 }
 
 pub async fn health_check() -> impl IntoResponse {
@@ -69,23 +161,131 @@ pub async fn favicon() -> impl IntoResponse {
     (headers, include_bytes!("../assets/favicon.ico").to_vec())
 }
 
-pub async fn reload(State(state): State<SharedState>) -> impl IntoResponse {
-    state.lock().unwrap().reload();
-    Redirect::to("/")
+pub async fn reload(State(state): State<SharedState>) -> Result<impl IntoResponse, AppError> {
+    lock_mutex(&state)?.reload()?;
+    Ok(Redirect::to("/"))
+}
+
+/// Serve a video's poster thumbnail, falling back to a placeholder image when
+/// generation hasn't finished yet (or `ffmpeg` isn't available).
+pub async fn thumbnail_handler(
+    Path(video_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<impl IntoResponse, AppError> {
+    let thumbnail_path = {
+        let state = lock_mutex(&state)?;
+        let video_id = state.canonical_video_id(&video_id);
+        let thumbnail_path = lock_mutex(&state.thumbnails)?.get(&video_id).cloned();
+        thumbnail_path
+    };
+
+    Ok(match thumbnail_path {
+        Some(path) => get_static_file(path).await?.into_response(),
+        None => placeholder_thumbnail().await.into_response(),
+    })
+}
+
+pub async fn placeholder_thumbnail() -> impl IntoResponse {
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        "image/svg+xml".parse().unwrap(),
+    );
+    (
+        headers,
+        include_bytes!("../assets/thumbnail_placeholder.svg").to_vec(),
+    )
+}
+
+#[derive(Serialize)]
+pub struct UploadResponse {
+    pub server_path: String,
+}
+
+/// Pick a filesystem path under `root` to store an uploaded file at, avoiding
+/// clobbering an existing file of the same name.
+async fn unique_upload_path(root: &StdPath, file_name: &str) -> PathBuf {
+    let original = StdPath::new(file_name);
+    let stem = original
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("upload");
+    let extension = original
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+
+    let mut candidate = root.join(format!("{stem}.{extension}"));
+    let mut suffix = 1;
+    while tokio::fs::metadata(&candidate).await.is_ok() {
+        candidate = root.join(format!("{stem}_{suffix}.{extension}"));
+        suffix += 1;
+    }
+    candidate
+}
+
+/// Accept a streaming multipart upload and index the result as a new video.
+///
+/// The first field that carries a file name is copied straight to disk under
+/// the configured asset root via `tokio::io::copy`, so the request body is
+/// never buffered in memory, and its server path is returned. Fields without
+/// a file name are skipped; fields after the stored one are not read.
+pub async fn upload_handler(
+    State(state): State<SharedState>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let root = lock_mutex(&state)?
+        .root()
+        .map(PathBuf::from)
+        .ok_or_else(|| AppError::Internal("Server has no configured asset root".to_string()))?;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| AppError::BadRequest(format!("Invalid multipart body: {}", err)))?
+    {
+        let Some(file_name) = field.file_name().map(|name| name.to_string()) else {
+            continue;
+        };
+
+        if !lock_mutex(&state)?.is_video_file(&file_name) {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "Unsupported file type: {}",
+                file_name
+            )));
+        }
+
+        let dest_path = unique_upload_path(&root, &file_name).await;
+        let mut dest_file = tokio::fs::File::create(&dest_path).await?;
+
+        let mut reader = StreamReader::new(
+            field.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err)),
+        );
+        tokio::io::copy(&mut reader, &mut dest_file).await?;
+
+        let server_path = lock_mutex(&state)?
+            .load_video(dest_path)
+            .ok_or_else(|| AppError::Internal("failed to index uploaded file".to_string()))?;
+        return Ok(Json(UploadResponse { server_path }));
+    }
+
+    Err(AppError::BadRequest(
+        "No file field found in multipart body".to_string(),
+    ))
 }
 
-pub async fn get_static_file(path: PathBuf) -> Result<Response<BoxBody>, (StatusCode, String)> {
-    let request = Request::builder().body(Body::empty()).unwrap();
+pub async fn get_static_file(path: PathBuf) -> Result<Response<BoxBody>, AppError> {
+    let request = Request::builder()
+        .body(Body::empty())
+        .map_err(|err| AppError::Internal(format!("Failed to build request: {}", err)))?;
 
     match ServeDir::new(path.clone()).oneshot(request).await {
         Ok(response) => Ok(response.map(boxed)),
-        Err(err) => {
-            eprintln!("Failed to open file: \nError: {}", err);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to open file".to_string(),
-            ))
-        }
+        Err(err) => Err(AppError::Internal(format!(
+            "Failed to open file {}: {}",
+            path.display(),
+            err
+        ))),
     }
 }
 
@@ -100,22 +300,259 @@ async fn handle_error(_err: std::io::Error) -> impl IntoResponse {
     (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong...")
 }
 
+/// Serve a single file with HTTP Range support, so clients can seek without
+/// re-downloading the whole response.
+///
+/// Only the first range of a multi-range request is honored (see
+/// [`video_server::range`]); an unsatisfiable range gets a `416` with
+/// `Content-Range: bytes */<total>` as required by RFC 7233.
+pub async fn serve_video_range(
+    path: PathBuf,
+    content_type: &str,
+    range_header: Option<&str>,
+) -> Result<Response<BoxBody>, AppError> {
+    let mut file = tokio::fs::File::open(&path).await.map_err(|err| {
+        AppError::Internal(format!("Failed to open file {}: {}", path.display(), err))
+    })?;
+    let file_size = file
+        .metadata()
+        .await
+        .map_err(|err| {
+            AppError::Internal(format!("Failed to stat file {}: {}", path.display(), err))
+        })?
+        .len();
+
+    let parsed = match range_header {
+        Some(raw) => range::parse_range_header(raw, file_size),
+        None => RangeParseResult::FullFile,
+    };
+
+    let response = match parsed {
+        RangeParseResult::Unsatisfiable => Response::builder()
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", file_size))
+            .body(boxed(Body::empty())),
+        RangeParseResult::Partial(range) => {
+            file.seek(std::io::SeekFrom::Start(range.start))
+                .await
+                .map_err(|err| {
+                    AppError::Internal(format!("Failed to seek file {}: {}", path.display(), err))
+                })?;
+            let stream = ReaderStream::new(file.take(range.len()));
+            Response::builder()
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_TYPE, content_type)
+                .header(header::ACCEPT_RANGES, "bytes")
+                .header(header::CONTENT_LENGTH, range.len())
+                .header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", range.start, range.end, file_size),
+                )
+                .body(boxed(StreamBody::new(stream)))
+        }
+        RangeParseResult::FullFile => {
+            let stream = ReaderStream::new(file);
+            Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, content_type)
+                .header(header::ACCEPT_RANGES, "bytes")
+                .header(header::CONTENT_LENGTH, file_size)
+                .body(boxed(StreamBody::new(stream)))
+        }
+    };
+
+    response.map_err(|err| AppError::Internal(err.to_string()))
+}
+
+/// Transcode `source` to fragmented MP4 via `ffmpeg`, streaming its `stdout`
+/// straight into the response while tee-ing the same bytes to `cache_path` so
+/// repeat requests can be served directly from disk.
+///
+/// If `cache_path` already exists, it's served as-is without re-transcoding,
+/// honoring `range_header` the same as any other on-disk file. A transcode in
+/// progress is always streamed from the start; seeking only becomes possible
+/// once it's cached.
+/// The `ffmpeg` child is killed automatically (`kill_on_drop`) if the client
+/// disconnects mid-stream, and a transcode that didn't finish cleanly never
+/// leaves a cache entry behind.
+pub async fn serve_transcoded(
+    source: PathBuf,
+    cache_path: PathBuf,
+    range_header: Option<&str>,
+) -> Result<Response<BoxBody>, AppError> {
+    if tokio::fs::metadata(&cache_path).await.is_ok() {
+        return serve_video_range(
+            cache_path,
+            transcode::FRAGMENTED_MP4_CONTENT_TYPE,
+            range_header,
+        )
+        .await;
+    }
+
+    if let Some(parent) = cache_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let mut child = tokio::process::Command::new("ffmpeg")
+        .arg("-i")
+        .arg(&source)
+        .args(transcode::FRAGMENTED_MP4_ARGS)
+        .arg("pipe:1")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| AppError::Internal(format!("Failed to start ffmpeg: {}", err)))?;
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| AppError::Internal("ffmpeg produced no stdout pipe".to_string()))?;
+
+    // Write to a `.part` file and rename on success, so a request that arrives
+    // mid-transcode never sees (and serves) a half-written cache entry.
+    let tmp_cache_path = cache_path.with_extension("part");
+    let mut cache_file = tokio::fs::File::create(&tmp_cache_path).await?;
+
+    let stream = try_stream! {
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let read = stdout.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            cache_file.write_all(&buf[..read]).await?;
+            yield axum::body::Bytes::copy_from_slice(&buf[..read]);
+        }
+        cache_file.flush().await?;
+        if child.wait().await?.success() {
+            tokio::fs::rename(&tmp_cache_path, &cache_path).await?;
+        } else {
+            let _ = tokio::fs::remove_file(&tmp_cache_path).await;
+        }
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, transcode::FRAGMENTED_MP4_CONTENT_TYPE)
+        .body(boxed(StreamBody::new(
+            stream.map_err(|err: std::io::Error| err),
+        )))
+        .map_err(|err| AppError::Internal(err.to_string()))
+}
+
 #[axum_macros::debug_handler]
 pub async fn video_handler(
     Path(video_id): Path<String>,
     State(state): State<SharedState>,
-) -> impl IntoResponse {
-    let file_path = state
-        .lock()
-        .unwrap()
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let video_id = lock_mutex(&state)?.canonical_video_id(&video_id);
+    let file_path = lock_mutex(&state)?
         .videos
         .get(&video_id)
-        .unwrap_or_else(|| panic!("Failed to find video with given id: {}", video_id.clone()))
-        .clone();
+        .cloned()
+        .ok_or_else(|| AppError::VideoNotFound(video_id.clone()))?;
+
+    let path = PathBuf::from(&file_path);
+    let range_header = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
 
-    drop(state);
+    // Computed into owned locals and the guard dropped before returning, so no
+    // MutexGuard is ever live across the `.await` below (which would make
+    // this handler's future non-`Send`).
+    let should_transcode = {
+        let guard = lock_mutex(&state)?;
+        guard.transcode_enabled() && !guard.is_web_playable(&path)
+    };
+    if should_transcode {
+        let root = lock_mutex(&state)?.root().map(PathBuf::from).ok_or_else(|| {
+            AppError::Internal("Server has no configured asset root".to_string())
+        })?;
+        let cache_path = root.join(".transcoded").join(format!("{}.mp4", video_id));
+        return serve_transcoded(path, cache_path, range_header).await;
+    }
 
-    get_static_file(PathBuf::from(&file_path)).await
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    let sniffed_content_type = lock_mutex(&state)?
+        .formats
+        .get(&video_id)
+        .and_then(|format| format.content_type());
+    let content_type = sniffed_content_type
+        .unwrap_or_else(|| VideoPlayerState::content_type_for_extension(extension));
+
+    serve_video_range(path, content_type, range_header).await
+}
+
+/// Watch the configured asset root for filesystem changes and incrementally
+/// update the video index, instead of requiring a manual `POST /reload`.
+///
+/// `notify`'s watcher callback runs on its own thread, outside any Tokio
+/// context, so each event borrows the current runtime `Handle` (captured up
+/// front) via [`tokio::runtime::Handle::enter`] before touching state — that's
+/// what lets `load_video`'s background thumbnail generation (`tokio::spawn`)
+/// work from there.
+fn spawn_watcher(state: SharedState) {
+    let Some(root) = lock_mutex(&state)
+        .ok()
+        .and_then(|s| s.root().map(PathBuf::from))
+    else {
+        return;
+    };
+    let handle = tokio::runtime::Handle::current();
+
+    std::thread::spawn(move || {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher =
+            match notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if let Ok(event) = event {
+                    let _ = tx.send(event);
+                }
+            }) {
+                Ok(watcher) => watcher,
+                Err(err) => {
+                    eprintln!("Failed to start filesystem watcher: {}", err);
+                    return;
+                }
+            };
+        if let Err(err) =
+            notify::Watcher::watch(&mut watcher, &root, notify::RecursiveMode::Recursive)
+        {
+            eprintln!("Failed to watch {}: {}", root.display(), err);
+            return;
+        }
+
+        for event in rx {
+            let _guard = handle.enter();
+            match event.kind {
+                notify::EventKind::Create(_) => {
+                    for path in event.paths {
+                        let Ok(mut state) = state.lock() else {
+                            continue;
+                        };
+                        if !state.is_hidden_path(&path)
+                            && path.is_file()
+                            && state.is_video_file_sniffed(&path)
+                        {
+                            state.load_video(path);
+                        }
+                    }
+                }
+                notify::EventKind::Remove(_) => {
+                    for path in event.paths {
+                        if let Ok(mut state) = state.lock() {
+                            state.remove_video_by_path(&path);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
 }
 
 pub fn set_up_logging() {
@@ -131,15 +568,24 @@ pub fn set_up_logging() {
 #[tokio::main]
 pub async fn main() {
     set_up_logging();
-    let config = VideoPlayerConfig::default();
+    let config = VideoPlayerConfig::parse();
     let state = Arc::new(Mutex::new(VideoPlayerState::build(&config)));
 
+    if config.enable_watch {
+        spawn_watcher(state.clone());
+    }
+
     let app = Router::new()
         .nest_service("/assets/", static_file_router())
         .route("/favicon.ico", get(favicon))
         .route("/video/:video_id", get(video_handler))
         .route("/", get(index))
         .route("/reload", post(reload))
+        .route(
+            "/upload",
+            post(upload_handler).layer(DefaultBodyLimit::max(config.max_upload_bytes)),
+        )
+        .route("/thumb/:video_id", get(thumbnail_handler))
         .route("/healthcheck", get(health_check))
         .layer(TraceLayer::new_for_http())
         .with_state(state);
@@ -0,0 +1,182 @@
+//! Parsing for HTTP `Range: bytes=...` headers.
+//!
+//! Kept free of any HTTP-framework types so it can be unit tested and reused
+//! regardless of how the response is ultimately streamed.
+
+/// A single, already-validated inclusive byte range within a file of known size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Number of bytes covered by this range.
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// Result of parsing a `Range` header against a known file size.
+pub enum RangeParseResult {
+    /// No `Range` header was present (or it didn't use the `bytes` unit): serve the whole file.
+    FullFile,
+    /// A single satisfiable range to serve.
+    ///
+    /// Only the first range of a multi-range request is honored; this crate never
+    /// emits `multipart/byteranges` responses.
+    Partial(ByteRange),
+    /// The requested range cannot be satisfied against `file_size`.
+    Unsatisfiable,
+}
+
+/// Parse a `Range: bytes=start-end` header value against `file_size`.
+pub fn parse_range_header(value: &str, file_size: u64) -> RangeParseResult {
+    let Some(ranges) = value.trim().strip_prefix("bytes=") else {
+        return RangeParseResult::FullFile;
+    };
+
+    let Some(first) = ranges.split(',').next() else {
+        return RangeParseResult::Unsatisfiable;
+    };
+
+    let Some((start, end)) = first.trim().split_once('-') else {
+        return RangeParseResult::Unsatisfiable;
+    };
+
+    let range = if start.is_empty() {
+        // Suffix range, e.g. "-500" means "the last 500 bytes".
+        let Ok(suffix_len) = end.parse::<u64>() else {
+            return RangeParseResult::Unsatisfiable;
+        };
+        if suffix_len == 0 {
+            return RangeParseResult::Unsatisfiable;
+        }
+        ByteRange {
+            start: file_size.saturating_sub(suffix_len),
+            end: file_size.saturating_sub(1),
+        }
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return RangeParseResult::Unsatisfiable;
+        };
+        let end = if end.is_empty() {
+            file_size.saturating_sub(1)
+        } else {
+            match end.parse::<u64>() {
+                Ok(end) => end,
+                Err(_) => return RangeParseResult::Unsatisfiable,
+            }
+        };
+        ByteRange { start, end }
+    };
+
+    if file_size == 0 || range.start > range.end || range.end >= file_size {
+        return RangeParseResult::Unsatisfiable;
+    }
+
+    RangeParseResult::Partial(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(result: RangeParseResult) -> ByteRange {
+        match result {
+            RangeParseResult::Partial(range) => range,
+            _ => panic!("expected a satisfiable partial range"),
+        }
+    }
+
+    #[test]
+    fn non_bytes_unit_serves_full_file() {
+        assert!(matches!(
+            parse_range_header("not-bytes=0-10", 100),
+            RangeParseResult::FullFile
+        ));
+    }
+
+    #[test]
+    fn start_and_end() {
+        let range = partial(parse_range_header("bytes=0-99", 200));
+        assert_eq!(range, ByteRange { start: 0, end: 99 });
+        assert_eq!(range.len(), 100);
+    }
+
+    #[test]
+    fn start_only_runs_to_end_of_file() {
+        let range = partial(parse_range_header("bytes=150-", 200));
+        assert_eq!(
+            range,
+            ByteRange {
+                start: 150,
+                end: 199
+            }
+        );
+    }
+
+    #[test]
+    fn suffix_range_is_last_n_bytes() {
+        let range = partial(parse_range_header("bytes=-500", 1000));
+        assert_eq!(
+            range,
+            ByteRange {
+                start: 500,
+                end: 999
+            }
+        );
+    }
+
+    #[test]
+    fn suffix_range_longer_than_file_clamps_to_start() {
+        let range = partial(parse_range_header("bytes=-5000", 1000));
+        assert_eq!(range, ByteRange { start: 0, end: 999 });
+    }
+
+    #[test]
+    fn zero_length_suffix_range_is_unsatisfiable() {
+        assert!(matches!(
+            parse_range_header("bytes=-0", 1000),
+            RangeParseResult::Unsatisfiable
+        ));
+    }
+
+    #[test]
+    fn only_first_range_of_a_multi_range_request_is_honored() {
+        let range = partial(parse_range_header("bytes=0-49,100-149", 200));
+        assert_eq!(range, ByteRange { start: 0, end: 49 });
+    }
+
+    #[test]
+    fn range_past_end_of_file_is_unsatisfiable() {
+        assert!(matches!(
+            parse_range_header("bytes=0-200", 100),
+            RangeParseResult::Unsatisfiable
+        ));
+    }
+
+    #[test]
+    fn inverted_range_is_unsatisfiable() {
+        assert!(matches!(
+            parse_range_header("bytes=50-10", 100),
+            RangeParseResult::Unsatisfiable
+        ));
+    }
+
+    #[test]
+    fn empty_file_is_always_unsatisfiable() {
+        assert!(matches!(
+            parse_range_header("bytes=0-0", 0),
+            RangeParseResult::Unsatisfiable
+        ));
+    }
+
+    #[test]
+    fn malformed_range_is_unsatisfiable() {
+        assert!(matches!(
+            parse_range_header("bytes=abc-def", 100),
+            RangeParseResult::Unsatisfiable
+        ));
+    }
+}
@@ -0,0 +1,41 @@
+//! Poster-thumbnail generation via `ffmpeg`, run off the request path.
+
+use std::path::{Path, PathBuf};
+
+/// Extract a single frame from `source` at `offset_secs` and write it as a JPEG
+/// under `cache_dir`, named after `server_path` so it can be looked up later.
+///
+/// Returns `Ok(None)` (rather than an error) when `ffmpeg` isn't installed, since
+/// thumbnailing is a best-effort enhancement and callers should just fall back to
+/// a placeholder image.
+pub async fn generate_thumbnail(
+    source: &Path,
+    cache_dir: &Path,
+    server_path: &str,
+    offset_secs: u32,
+) -> std::io::Result<Option<PathBuf>> {
+    tokio::fs::create_dir_all(cache_dir).await?;
+    // server paths look like "3.mp4"; turn the dot into an underscore so the
+    // cache file doesn't look like it has a ".mp4.jpg" double extension.
+    let dest = cache_dir.join(format!("{}.jpg", server_path.replace('.', "_")));
+
+    let status = match tokio::process::Command::new("ffmpeg")
+        .arg("-y")
+        .args(["-ss", &offset_secs.to_string()])
+        .arg("-i")
+        .arg(source)
+        .args(["-frames:v", "1", "-vf", "scale=320:-1"])
+        .arg(&dest)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await
+    {
+        Ok(status) => status,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    Ok(status.success().then_some(dest))
+}
@@ -0,0 +1,8 @@
+//! On-the-fly transcoding to a browser-friendly codec via `ffmpeg`.
+
+/// Arguments that make `ffmpeg` emit fragmented MP4 on `stdout`, suitable for
+/// piping straight into an HTTP response body.
+pub const FRAGMENTED_MP4_ARGS: &[&str] = &["-movflags", "frag_keyframe+empty_moov", "-f", "mp4"];
+
+/// `Content-Type` to serve a fragmented-MP4 transcode under.
+pub const FRAGMENTED_MP4_CONTENT_TYPE: &str = "video/mp4";
@@ -0,0 +1,189 @@
+//! Group videos by folder for the hierarchical (non-flat) index view.
+
+/// One folder in the hierarchical video index.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryNode {
+    pub name: String,
+    pub subdirs: Vec<DirectoryNode>,
+    pub videos: Vec<VideoEntry>,
+}
+
+/// A single video shown under a [`DirectoryNode`].
+#[derive(Debug, Clone)]
+pub struct VideoEntry {
+    pub server_path: String,
+    pub display_name: String,
+}
+
+/// Build a nested directory tree out of `(relative_path, video)` pairs,
+/// splitting each relative path on `/` to determine its folder nesting. The
+/// last path component is treated as the file name and discarded, since the
+/// video's own display name is carried separately on `video`.
+pub fn build_tree<'a, I>(entries: I) -> DirectoryNode
+where
+    I: IntoIterator<Item = (&'a str, VideoEntry)>,
+{
+    let mut root = DirectoryNode::default();
+    for (relative_path, video) in entries {
+        let mut components: Vec<&str> = relative_path.split('/').collect();
+        components.pop();
+
+        let mut node = &mut root;
+        for dir in components {
+            let idx = match node.subdirs.iter().position(|subdir| subdir.name == dir) {
+                Some(idx) => idx,
+                None => {
+                    node.subdirs.push(DirectoryNode {
+                        name: dir.to_string(),
+                        ..Default::default()
+                    });
+                    node.subdirs.len() - 1
+                }
+            };
+            node = &mut node.subdirs[idx];
+        }
+        node.videos.push(video);
+    }
+    sort_tree(&mut root);
+    root
+}
+
+fn sort_tree(node: &mut DirectoryNode) {
+    node.subdirs.sort_by(|a, b| a.name.cmp(&b.name));
+    node.videos
+        .sort_by(|a, b| a.display_name.cmp(&b.display_name));
+    for subdir in &mut node.subdirs {
+        sort_tree(subdir);
+    }
+}
+
+/// One row of a [`DirectoryNode`] tree flattened into pre-order, for
+/// rendering with a single non-recursive `{% for %}` loop: askama doesn't
+/// support a macro calling itself recursively, so the index template can't
+/// walk a [`DirectoryNode`] directly.
+///
+/// Exactly one of `folder_name`/`video` is set, indicating which kind of row
+/// this is; `depth` is how many ancestor folders to indent under.
+#[derive(Debug, Clone)]
+pub struct TreeRow {
+    pub depth: usize,
+    pub folder_name: Option<String>,
+    pub video: Option<VideoEntry>,
+}
+
+/// Flatten `root` into a pre-order sequence of [`TreeRow`]s: each folder is
+/// immediately followed by its subfolders (depth first) and then its own
+/// videos, the same nesting a recursive walk of the tree would print.
+pub fn flatten_tree(root: &DirectoryNode) -> Vec<TreeRow> {
+    let mut rows = Vec::new();
+    flatten_into(root, 0, &mut rows);
+    rows
+}
+
+fn flatten_into(node: &DirectoryNode, depth: usize, rows: &mut Vec<TreeRow>) {
+    for subdir in &node.subdirs {
+        rows.push(TreeRow {
+            depth,
+            folder_name: Some(subdir.name.clone()),
+            video: None,
+        });
+        flatten_into(subdir, depth + 1, rows);
+    }
+    for video in &node.videos {
+        rows.push(TreeRow {
+            depth,
+            folder_name: None,
+            video: Some(video.clone()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(server_path: &str, display_name: &str) -> VideoEntry {
+        VideoEntry {
+            server_path: server_path.to_string(),
+            display_name: display_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn top_level_file_has_no_subdirs() {
+        let root = build_tree([("movie.mp4", entry("0", "movie.mp4"))]);
+        assert!(root.subdirs.is_empty());
+        assert_eq!(root.videos.len(), 1);
+        assert_eq!(root.videos[0].display_name, "movie.mp4");
+    }
+
+    #[test]
+    fn nests_by_folder_and_discards_the_file_name_component() {
+        let root = build_tree([("shows/s1/e1.mp4", entry("0", "e1.mp4"))]);
+        assert_eq!(root.videos.len(), 0);
+        assert_eq!(root.subdirs.len(), 1);
+        let shows = &root.subdirs[0];
+        assert_eq!(shows.name, "shows");
+        assert_eq!(shows.subdirs.len(), 1);
+        let s1 = &shows.subdirs[0];
+        assert_eq!(s1.name, "s1");
+        assert_eq!(s1.videos.len(), 1);
+        assert_eq!(s1.videos[0].display_name, "e1.mp4");
+    }
+
+    #[test]
+    fn shares_a_folder_node_across_multiple_entries() {
+        let root = build_tree([
+            ("shows/s1/e1.mp4", entry("0", "e1.mp4")),
+            ("shows/s1/e2.mp4", entry("1", "e2.mp4")),
+            ("shows/s2/e1.mp4", entry("2", "e1.mp4")),
+        ]);
+        let shows = &root.subdirs[0];
+        assert_eq!(shows.subdirs.len(), 2);
+        let s1 = shows.subdirs.iter().find(|d| d.name == "s1").unwrap();
+        assert_eq!(s1.videos.len(), 2);
+    }
+
+    #[test]
+    fn sorts_subdirs_and_videos_by_name() {
+        let root = build_tree([
+            ("b/v.mp4", entry("0", "v.mp4")),
+            ("a/v.mp4", entry("1", "v.mp4")),
+            ("charlie.mp4", entry("2", "charlie.mp4")),
+            ("alpha.mp4", entry("3", "alpha.mp4")),
+        ]);
+        let subdir_names: Vec<_> = root.subdirs.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(subdir_names, vec!["a", "b"]);
+        let video_names: Vec<_> = root.videos.iter().map(|v| v.display_name.as_str()).collect();
+        assert_eq!(video_names, vec!["alpha.mp4", "charlie.mp4"]);
+    }
+
+    #[test]
+    fn flatten_is_pre_order_with_folders_before_their_videos() {
+        let root = build_tree([
+            ("shows/s1/e1.mp4", entry("0", "e1.mp4")),
+            ("top.mp4", entry("1", "top.mp4")),
+        ]);
+        let rows = flatten_tree(&root);
+
+        let shapes: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.depth,
+                    row.folder_name.as_deref(),
+                    row.video.as_ref().map(|v| v.display_name.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            shapes,
+            vec![
+                (0, Some("shows"), None),
+                (1, Some("s1"), None),
+                (2, None, Some("e1.mp4")),
+                (0, None, Some("top.mp4")),
+            ]
+        );
+    }
+}